@@ -1,31 +1,55 @@
+// pool for building command buffers off the main thread; buffers are created
+// on the main thread (through `Context`), not by the pool
+mod worker;
+use worker::WorkerPool;
+
+// physical device and queue family selection
+mod device_select;
+use device_select::QueueCapability;
+
+// task graph: schedules passes with automatic layout transitions
+mod graph;
+use graph::{Access as GraphAccess, Graph, ImageUse, Task as GraphTask};
+
+// bundles instance/device/queue and the buffer/submit boilerplate
+mod context;
+use context::Context;
+
+use std::sync::Arc;
+
 use vulkano::instance::{Instance, InstanceExtensions};
 use vulkano::Version;
 
-// Create physical devices
-use vulkano::device::physical::PhysicalDevice;
-
 // For devices
-use vulkano::device::{Device, DeviceExtensions, Features};
+use vulkano::device::{Device, Features};
 
 // For accessable buffer
-use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::buffer::BufferUsage;
 
 // For command buffer
-use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer};
 
 // For image
-use vulkano::image::{ImageDimensions, StorageImage};
+use vulkano::image::{ImageAccess, ImageDimensions, ImageLayout, ImageUsage, StorageImage};
 use vulkano::format::{Format, ClearValue};
 use image::{ImageBuffer, Rgba};
 
 // For compute image
 use vulkano::image::view::ImageView;
 
+// For the windowed swapchain mode
+use vulkano::swapchain::{self, AcquireError, Swapchain, SwapchainCreationError};
+use vulkano::sync::FlushError;
+use vulkano_win::VkSurfaceBuild;
+use winit::event::{Event, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop};
+use winit::window::WindowBuilder;
+
 // Submit commands
 use vulkano::sync;
 use vulkano::sync::GpuFuture;
 
-// Pipline 
+// Pipline
 use vulkano::pipeline::ComputePipeline;
 use vulkano::pipeline::Pipeline;
 use vulkano::pipeline::PipelineBindPoint;
@@ -38,33 +62,68 @@ enum PracticeType
     Simple,
     Compute,
     Image,
+    // headless: dispatches the Mandelbrot shader into an offscreen StorageImage,
+    // copies it back through the task graph, and saves image.png
+    ComputeImageOffscreen,
+    // bare windowed swapchain presentation: no compute workload, each frame
+    // is just cleared to a flat color and presented in a resizable window
+    Window,
+    // live Mandelbrot dispatched straight into the swapchain images and
+    // presented in a resizable window instead of dumping a PNG
     ComputeImage,
 }
 
 fn main()
 {
-    // let required_extensions = vulkano_win::required_extensions();
-    let instance =  Instance::new(None, Version::V1_1, &InstanceExtensions::none(), None)
-        .expect("failed to create an instance");
+    let buffer_type = PracticeType::ComputeImageOffscreen;
+
+    // the windowed modes need an event loop and a surface, and the instance
+    // has to be created with the extensions the surface requires
+    let event_loop = EventLoop::new();
+    let instance = if matches!(buffer_type, PracticeType::ComputeImage | PracticeType::Window) {
+        let required_extensions = vulkano_win::required_extensions();
+        Instance::new(None, Version::V1_1, &required_extensions, None)
+            .expect("failed to create an instance")
+    } else {
+        Instance::new(None, Version::V1_1, &InstanceExtensions::none(), None)
+            .expect("failed to create an instance")
+    };
 
-    let physical = PhysicalDevice::enumerate(&instance).next().expect("no device available");
+    let surface = if matches!(buffer_type, PracticeType::ComputeImage | PracticeType::Window) {
+        Some(WindowBuilder::new()
+            .build_vk_surface(&event_loop, instance.clone())
+            .expect("failed to create window surface"))
+    } else {
+        None
+    };
+
+    // what each practice mode needs from its queue family: the windowed
+    // Window and ComputeImage modes present to the surface, the other
+    // compute-only modes just dispatch
+    let capability = match (&buffer_type, &surface) {
+        (PracticeType::ComputeImage, Some(surface)) | (PracticeType::Window, Some(surface)) => {
+            QueueCapability::GraphicsPresent(surface)
+        }
+        (PracticeType::Compute, _) | (PracticeType::ComputeImageOffscreen, _) => QueueCapability::Compute,
+        _ => QueueCapability::Graphics,
+    };
 
-    // queue for cpu operations
-    // for family in physical.queue_families() {
-    //     println!("Found a queue family with {:?} queue(s)", family.queues_count());
-    // }
-    let queue_families = physical.queue_families()
-        .find(|&q| q.supports_graphics())
-        .expect("coundn't find a graphical queue family");
+    let required_extensions = device_select::required_extensions_for(&capability);
+    let (physical, queue_families, device_extensions) =
+        device_select::select_device(&instance, &required_extensions, capability);
 
     let (device, mut queues) = {
-        Device::new(physical, &Features::none(), &DeviceExtensions::none(), [(queue_families, 0.5)].iter().cloned())
+        Device::new(physical, &Features::none(), &device_extensions, [(queue_families, 0.5)].iter().cloned())
             .expect("Failed to create device")
     };
 
     let queue = queues.next().unwrap();
 
-    let buffer_type = PracticeType::ComputeImage;
+    // builds command buffers off the main thread; each worker keeps its own
+    // clone of the device/queue handles
+    let pool = WorkerPool::new(device.clone(), queue.clone(), 4);
+
+    let ctx = Context::new(device.clone(), queue.clone());
 
     #[allow(unreachable_patterns, unused_variables)]
     match buffer_type
@@ -72,34 +131,16 @@ fn main()
         PracticeType::Simple => {
             // Simple example
             // accesable buffer for store and operate datas
-            let source_content = 0..64; 
-            let source = CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::all(), false, source_content)
-                .expect("failed to create buffer");
-
-            let destination_content = (0..64).map(|_| 0);
-            let destination = CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::all(), false, destination_content)
-                .expect("failed to create buffer");
-
-            // Command buffer
-            let mut builder = AutoCommandBufferBuilder::primary(
-                device.clone(),
-                queue.family(), 
-                CommandBufferUsage::OneTimeSubmit,
-            ).unwrap();
-
-            builder.copy_buffer(source.clone(), destination.clone()).unwrap();
-
-            let command_buffer = builder.build().unwrap();
-
-            // Subimit and try get data when GPU finished operation
-            let future = sync::now(device.clone())
-                .then_execute(queue.clone(), command_buffer)
-                .unwrap()
-                .then_signal_fence_and_flush() // signal to the cpu and start executing
-                .unwrap();
+            let source: Vec<i32> = (0..64).collect();
+            let source = ctx.create_buffer_init(&source, BufferUsage::all());
+            let destination = ctx.create_zeroed_buffer::<i32>(64, BufferUsage::all());
 
-            // wait for GPU
-            future.wait(None).unwrap();
+            // `source`/`destination` are `Arc`s so they're cheap to share
+            // with the worker thread that builds the command buffer
+            let (worker_source, worker_destination) = (source.clone(), destination.clone());
+            ctx.submit_now(&pool, move |builder| {
+                builder.copy_buffer(worker_source, worker_destination).unwrap();
+            });
 
             // Read data from destination
             let src_content = source.read().unwrap();
@@ -110,9 +151,8 @@ fn main()
 
         PracticeType::Compute => {
             // Compute example
-            let data_iter = 0..65535;
-            let data_buffer = CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::all(), false, data_iter)
-                .expect("failed to create buffer");
+            let data: Vec<u32> = (0..65535).collect();
+            let data_buffer = ctx.create_buffer_init(&data, BufferUsage::all());
 
             mod cs {
                 vulkano_shaders::shader!{
@@ -156,30 +196,16 @@ fn main()
                 [WriteDescriptorSet::buffer(0, data_buffer.clone())],
                 ).unwrap();
 
-            let mut builder = AutoCommandBufferBuilder::primary(
-                    device.clone(),
-                    queue.family(), 
-                    CommandBufferUsage::OneTimeSubmit,
-                ).unwrap();
-
-            builder.bind_pipeline_compute(compute_pipeline.clone())
-                .bind_descriptor_sets(
-                    PipelineBindPoint::Compute, 
-                    compute_pipeline.layout().clone(), 
-                    0, 
-                    set)
-                .dispatch([1024, 1, 1])
-                .unwrap();
-
-            let command_buffer = builder.build().unwrap();
-
-            let future = sync::now(device.clone())
-                    .then_execute(queue.clone(), command_buffer)
-                    .unwrap()
-                    .then_signal_fence_and_flush()
+            ctx.submit_now(&pool, move |builder| {
+                builder.bind_pipeline_compute(compute_pipeline.clone())
+                    .bind_descriptor_sets(
+                        PipelineBindPoint::Compute,
+                        compute_pipeline.layout().clone(),
+                        0,
+                        set)
+                    .dispatch([1024, 1, 1])
                     .unwrap();
-
-            future.wait(None).unwrap();
+            });
 
             let content = data_buffer.read().unwrap();
 
@@ -202,40 +228,23 @@ fn main()
             ).unwrap();
 
             // Create a buffer to store image
-            let buf  = CpuAccessibleBuffer::from_iter(device.clone(),
-                BufferUsage::all(),
-                false,
-                (0..1024 * 1024 * 4).map(|_| 0u8),
-            ).expect("failed to create buffer");
-
-            let mut builder = AutoCommandBufferBuilder::primary(
-                device.clone(),
-                queue.family(),
-                CommandBufferUsage::OneTimeSubmit,
-            ).unwrap();
+            let buf = ctx.create_zeroed_buffer::<u8>(1024 * 1024 * 4, BufferUsage::all());
 
-            builder.clear_color_image(image.clone(),
-                ClearValue::Float([0.0, 0.0, 1.0, 1.0]))
-                .unwrap()
-                .copy_image_to_buffer(image.clone(), buf.clone())
-                .unwrap();
-
-            let command_buffer = builder.build().unwrap();
-
-            let future = sync::now(device.clone())
-                .then_execute(queue.clone(), command_buffer)
-                .unwrap()
-                .then_signal_fence_and_flush()
-                .unwrap();
-
-            future.wait(None).unwrap();
+            let (worker_image, worker_buf) = (image.clone(), buf.clone());
+            ctx.submit_now(&pool, move |builder| {
+                builder.clear_color_image(worker_image.clone(),
+                    ClearValue::Float([0.0, 0.0, 1.0, 1.0]))
+                    .unwrap()
+                    .copy_image_to_buffer(worker_image, worker_buf)
+                    .unwrap();
+            });
 
             let buffer_content = buf.read().unwrap();
             let showing_image = ImageBuffer::<Rgba<u8>, _>::from_raw(1024, 1024, &buffer_content[..]).unwrap();
             showing_image.save("image.png").unwrap();
         }
 
-        PracticeType::ComputeImage => {
+        PracticeType::ComputeImageOffscreen => {
             let image = StorageImage::new(
                 device.clone(), ImageDimensions::Dim2d {
                     width: 1024,
@@ -305,44 +314,440 @@ fn main()
                 [WriteDescriptorSet::image_view(0, view.clone())],
             ).unwrap();
 
-            let buf = CpuAccessibleBuffer::from_iter(device.clone(), 
-                BufferUsage::all(),
-                false,
-                (0..1024 * 1024 * 4).map(|_| 0u8),
-            ).expect("failed to create buffer");
+            let buf = ctx.create_zeroed_buffer::<u8>(1024 * 1024 * 4, BufferUsage::all());
+
+            // reference example for the task graph: a dispatch that writes
+            // the image, then a copy that reads it back, with the layout
+            // transition between them inserted automatically
+            let dispatch_image: Arc<dyn ImageAccess> = image.clone();
+            let copy_image: Arc<dyn ImageAccess> = image.clone();
+            let worker_buf = buf.clone();
+
+            let mut pipeline_graph = Graph::new();
+            pipeline_graph.add_task(GraphTask::new(
+                vec![ImageUse::new("mandelbrot_image", dispatch_image, GraphAccess::Write, ImageLayout::General)],
+                move |builder: &mut AutoCommandBufferBuilder<_>| {
+                    builder
+                        .bind_pipeline_compute(compute_pipeline.clone())
+                        .bind_descriptor_sets(
+                            PipelineBindPoint::Compute,
+                            compute_pipeline.layout().clone(),
+                            0,
+                            set,
+                        )
+                        .dispatch([1024 / 8, 1024 / 8, 1])
+                        .unwrap();
+                },
+            ));
+            pipeline_graph.add_task(GraphTask::new(
+                vec![ImageUse::new("mandelbrot_image", copy_image, GraphAccess::Read, ImageLayout::TransferSrcOptimal)],
+                move |builder: &mut AutoCommandBufferBuilder<_>| {
+                    builder.copy_image_to_buffer(image, worker_buf).unwrap();
+                },
+            ));
+
+            ctx.submit_now(&pool, move |builder| {
+                pipeline_graph.compile(builder, |builder, image, from, to| {
+                    // a freshly-created StorageImage starts out undefined;
+                    // anything else only needs General, which the dispatch
+                    // above transitions into via a clear
+                    if to == ImageLayout::General && from != ImageLayout::General {
+                        builder.clear_color_image(image.clone(), ClearValue::Float([0.0, 0.0, 0.0, 1.0])).unwrap();
+                    }
+                    // General -> TransferSrcOptimal doesn't need an explicit
+                    // command here: the builder's own sync tracking inserts
+                    // the barrier when `copy_image_to_buffer` is recorded
+                });
+            });
 
-            let mut builder = AutoCommandBufferBuilder::primary(
-                device.clone(), 
-                queue.family(), 
-                CommandBufferUsage::OneTimeSubmit,
-            ).unwrap();
+            let buffer_content = buf.read().unwrap();
+            let out_image = ImageBuffer::<Rgba<u8>, _>::from_raw(1024, 1024, &buffer_content[..]).unwrap();
+            out_image.save("image.png").unwrap();
+        }
 
-            builder
-                .bind_pipeline_compute(compute_pipeline.clone())
-                .bind_descriptor_sets(
-                    PipelineBindPoint::Compute,
-                    compute_pipeline.layout().clone(),
-                    0,
-                    set,
-                )
-                .dispatch([1024 / 8, 1024 / 8, 1])
-                .unwrap()
-                .copy_image_to_buffer(image.clone(), buf.clone())
-                .unwrap();
-            
-            let command_buffer = builder.build().unwrap();
+        PracticeType::Window => {
+            let surface = surface.expect("windowed Window mode requires a surface");
+
+            let (mut swapchain, mut images) = {
+                let caps = surface.capabilities(physical)
+                    .expect("failed to get surface capabilities");
+                let dimensions: [u32; 2] = surface.window().inner_size().into();
+                let composite_alpha = caps.supported_composite_alpha.iter().next().unwrap();
+                let format = caps.supported_formats[0].0;
+
+                Swapchain::start(device.clone(), surface.clone())
+                    .num_images(caps.min_image_count)
+                    .format(format)
+                    .dimensions(dimensions)
+                    // clear_color_image (used to paint each frame below) requires
+                    // VK_IMAGE_USAGE_TRANSFER_DST_BIT on top of the usual color attachment
+                    .usage(ImageUsage { transfer_destination: true, ..ImageUsage::color_attachment() })
+                    .sharing_mode(&queue)
+                    .composite_alpha(composite_alpha)
+                    .build()
+                    .expect("failed to create swapchain")
+            };
+
+            // true whenever the window was resized or the swapchain went out of date,
+            // so the next iteration of the loop rebuilds it before acquiring an image
+            let mut recreate_swapchain = false;
+            let mut previous_frame_end = Some(sync::now(device.clone()).boxed());
+
+            event_loop.run(move |event, _, control_flow| {
+                match event {
+                    Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => {
+                        *control_flow = ControlFlow::Exit;
+                    }
+                    Event::WindowEvent { event: WindowEvent::Resized(_), .. } => {
+                        recreate_swapchain = true;
+                    }
+                    Event::RedrawEventsCleared => {
+                        previous_frame_end.as_mut().unwrap().cleanup_finished();
+
+                        if recreate_swapchain {
+                            let dimensions: [u32; 2] = surface.window().inner_size().into();
+                            let (new_swapchain, new_images) = match swapchain.recreate().dimensions(dimensions).build() {
+                                Ok(r) => r,
+                                // the window is being resized on another platform event,
+                                // we'll just try again on the next frame
+                                Err(SwapchainCreationError::UnsupportedDimensions) => return,
+                                Err(e) => panic!("failed to recreate swapchain: {:?}", e),
+                            };
+
+                            swapchain = new_swapchain;
+                            images = new_images;
+                            recreate_swapchain = false;
+                        }
+
+                        let (image_num, suboptimal, acquire_future) =
+                            match swapchain::acquire_next_image(swapchain.clone(), None) {
+                                Ok(r) => r,
+                                Err(AcquireError::OutOfDate) => {
+                                    recreate_swapchain = true;
+                                    return;
+                                }
+                                Err(e) => panic!("failed to acquire next image: {:?}", e),
+                            };
+
+                        if suboptimal {
+                            recreate_swapchain = true;
+                        }
+
+                        // no compute workload in this mode: just clear the
+                        // acquired image to a flat color and present it, to
+                        // prove out resizable-window swapchain presentation
+                        // on its own before any shader gets involved; built
+                        // on a worker thread like every other practice mode
+                        let worker_image = images[image_num].clone();
+                        let command_buffer = pool.submit(move |device, queue| {
+                            let mut builder = AutoCommandBufferBuilder::primary(
+                                device.clone(),
+                                queue.family(),
+                                CommandBufferUsage::OneTimeSubmit,
+                            ).unwrap();
+
+                            builder
+                                .clear_color_image(worker_image, ClearValue::Float([0.05, 0.05, 0.08, 1.0]))
+                                .unwrap();
+
+                            builder.build().unwrap()
+                        }).recv().expect("worker thread dropped the reply channel");
+
+                        let future = previous_frame_end.take().unwrap()
+                            .join(acquire_future)
+                            .then_execute(queue.clone(), command_buffer)
+                            .unwrap()
+                            .then_swapchain_present(queue.clone(), swapchain.clone(), image_num)
+                            .then_signal_fence_and_flush();
+
+                        previous_frame_end = match future {
+                            Ok(future) => Some(future.boxed()),
+                            Err(FlushError::OutOfDate) => {
+                                recreate_swapchain = true;
+                                Some(sync::now(device.clone()).boxed())
+                            }
+                            Err(e) => {
+                                println!("failed to flush future: {:?}", e);
+                                Some(sync::now(device.clone()).boxed())
+                            }
+                        };
+                    }
+                    _ => (),
+                }
+            });
+        }
+
+        PracticeType::ComputeImage => {
+            let surface = surface.expect("windowed ComputeImage mode requires a surface");
+
+            let (mut swapchain, mut images) = {
+                let caps = surface.capabilities(physical)
+                    .expect("failed to get surface capabilities");
+                let dimensions: [u32; 2] = surface.window().inner_size().into();
+                let composite_alpha = caps.supported_composite_alpha.iter().next().unwrap();
+                let format = caps.supported_formats[0].0;
+
+                Swapchain::start(device.clone(), surface.clone())
+                    .num_images(caps.min_image_count)
+                    .format(format)
+                    .dimensions(dimensions)
+                    // the compute shader writes into the swapchain images directly, so
+                    // they need storage usage on top of the usual color attachment, and
+                    // transfer_destination since the per-frame clear (that works around
+                    // the ImageNotInitialized layout problem below) is a transfer command
+                    .usage(ImageUsage { storage: true, transfer_destination: true, ..ImageUsage::color_attachment() })
+                    .sharing_mode(&queue)
+                    .composite_alpha(composite_alpha)
+                    .build()
+                    .expect("failed to create swapchain")
+            };
 
-            let future = sync::now(device.clone())
-                .then_execute(queue.clone(), command_buffer)
+            mod cs {
+                vulkano_shaders::shader!{
+                    ty: "compute",
+                    src: "
+                        #version 450
+
+                        layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+                        layout(set = 0, binding = 0, rgba8) uniform writeonly image2D img;
+
+                        layout(push_constant) uniform PushConstants {
+                            float time;
+                        } pc;
+
+                        void main() {
+                            vec2 norm_coordinates = (gl_GlobalInvocationID.xy + vec2(0.5)) / vec2(imageSize(img));
+                            vec2 c = (norm_coordinates - vec2(0.5)) * 2.0 - vec2(0.5, 0.0);
+                            c += 0.05 * vec2(cos(pc.time), sin(pc.time));
+
+                            vec2 z = vec2(0.0, 0.0);
+                            float i;
+                            for (i = 0.0; i < 1.0; i += 0.005) {
+                                z = vec2(
+                                    z.x * z.x - z.y * z.y + c.x,
+                                    z.y * z.x + z.x * z.y + c.y
+                                );
+
+                                if (length(z) > 4.0) {
+                                    break;
+                                }
+                            }
+
+                            vec4 to_write = vec4(vec3(i), 1.0);
+                            imageStore(img, ivec2(gl_GlobalInvocationID.xy), to_write);
+                        }
+                    "
+                }
+            }
+
+            let shader = cs::load(device.clone())
+                .expect("Failed to create shader module!");
+
+            let compute_pipeline = ComputePipeline::new(
+                device.clone(),
+                shader.entry_point("main").unwrap(),
+                &(),
+                None,
+                |_| (),
+            ).expect("Failed to create compute pipeline");
+
+            let layout = compute_pipeline
+                .layout()
+                .descriptor_set_layouts()
+                .get(0)
                 .unwrap()
-                .then_signal_fence_and_flush()
-                .unwrap();
+                .clone();
+
+            // one descriptor set per swapchain image, rebuilt whenever the swapchain
+            // (and therefore its image views) is recreated
+            let mut image_sets: Vec<_> = images.iter()
+                .map(|image| {
+                    let view = ImageView::new(image.clone()).unwrap();
+                    PersistentDescriptorSet::new(layout.clone(), [WriteDescriptorSet::image_view(0, view)])
+                        .unwrap()
+                })
+                .collect();
+
+            // a command buffer whose build was kicked off on a previous
+            // iteration, so the worker thread gets a whole frame's worth of
+            // GPU execute + present + vsync wait to finish it instead of the
+            // main thread blocking on `submit(...).recv()` the moment it's needed
+            struct PendingFrame {
+                image_num: usize,
+                suboptimal: bool,
+                acquire_future: vulkano::swapchain::SwapchainAcquireFuture<winit::window::Window>,
+                receiver: std::sync::mpsc::Receiver<PrimaryAutoCommandBuffer>,
+            }
 
-            future.wait(None).unwrap();
+            // acquires the next swapchain image and hands its command buffer off
+            // to the worker pool without waiting for the result; a plain fn
+            // rather than a closure so every piece of state it touches is
+            // explicit, since it's called both to prime the first frame and,
+            // every frame after, to start building the *next* one ahead of time
+            fn acquire_frame<I>(
+                swapchain: &Arc<Swapchain<winit::window::Window>>,
+                images: &[Arc<I>],
+                image_sets: &[Arc<PersistentDescriptorSet>],
+                compute_pipeline: &Arc<ComputePipeline>,
+                pool: &WorkerPool,
+                start_time: &std::time::Instant,
+            ) -> Result<PendingFrame, AcquireError>
+            where
+                I: ImageAccess + Send + Sync + 'static,
+            {
+                let (image_num, suboptimal, acquire_future) =
+                    swapchain::acquire_next_image(swapchain.clone(), None)?;
+
+                let push_constants = cs::ty::PushConstants {
+                    time: start_time.elapsed().as_secs_f32(),
+                };
+
+                let worker_image = images[image_num].clone();
+                let worker_set = image_sets[image_num].clone();
+                let worker_pipeline = compute_pipeline.clone();
+                let receiver = pool.submit(move |device, queue| {
+                    let mut builder = AutoCommandBufferBuilder::primary(
+                        device.clone(),
+                        queue.family(),
+                        CommandBufferUsage::OneTimeSubmit,
+                    ).unwrap();
+
+                    // a freshly-acquired swapchain image is in an undefined/PresentSrc
+                    // layout; binding it as a writeonly storage image without first
+                    // transitioning it to General fails with ImageNotInitialized, so
+                    // clear it into General before the dispatch...
+                    builder
+                        .clear_color_image(worker_image.clone(), ClearValue::Float([0.0, 0.0, 0.0, 1.0]))
+                        .unwrap()
+                        .bind_pipeline_compute(worker_pipeline.clone())
+                        .bind_descriptor_sets(
+                            PipelineBindPoint::Compute,
+                            worker_pipeline.layout().clone(),
+                            0,
+                            worker_set,
+                        )
+                        .push_constants(worker_pipeline.layout().clone(), 0, push_constants)
+                        .dispatch([
+                            (worker_image.dimensions().width() + 7) / 8,
+                            (worker_image.dimensions().height() + 7) / 8,
+                            1,
+                        ])
+                        .unwrap();
+                    // ...the present engine expects PresentSrc again, which vulkano's
+                    // sync tracking transitions the image back to automatically once
+                    // `then_swapchain_present` is used below.
+
+                    builder.build().unwrap()
+                });
+
+                Ok(PendingFrame { image_num, suboptimal, acquire_future, receiver })
+            }
 
-            let buffer_content = buf.read().unwrap();
-            let out_image = ImageBuffer::<Rgba<u8>, _>::from_raw(1024, 1024, &buffer_content[..]).unwrap();
-            out_image.save("image.png").unwrap();
+            // true whenever the window was resized or the swapchain went out of date,
+            // so the next iteration of the loop rebuilds it before acquiring an image
+            let mut recreate_swapchain = false;
+            let mut previous_frame_end = Some(sync::now(device.clone()).boxed());
+            let mut pending_build: Option<PendingFrame> = None;
+            let start_time = std::time::Instant::now();
+
+            event_loop.run(move |event, _, control_flow| {
+                match event {
+                    Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => {
+                        *control_flow = ControlFlow::Exit;
+                    }
+                    Event::WindowEvent { event: WindowEvent::Resized(_), .. } => {
+                        recreate_swapchain = true;
+                    }
+                    Event::RedrawEventsCleared => {
+                        previous_frame_end.as_mut().unwrap().cleanup_finished();
+
+                        if recreate_swapchain {
+                            let dimensions: [u32; 2] = surface.window().inner_size().into();
+                            let (new_swapchain, new_images) = match swapchain.recreate().dimensions(dimensions).build() {
+                                Ok(r) => r,
+                                // the window is being resized on another platform event,
+                                // we'll just try again on the next frame
+                                Err(SwapchainCreationError::UnsupportedDimensions) => return,
+                                Err(e) => panic!("failed to recreate swapchain: {:?}", e),
+                            };
+
+                            swapchain = new_swapchain;
+                            images = new_images;
+                            image_sets = images.iter()
+                                .map(|image| {
+                                    let view = ImageView::new(image.clone()).unwrap();
+                                    PersistentDescriptorSet::new(layout.clone(), [WriteDescriptorSet::image_view(0, view)])
+                                        .unwrap()
+                                })
+                                .collect();
+                            recreate_swapchain = false;
+                            // a pending build (if any) was recorded against the
+                            // old image views and descriptor sets, so it can't be
+                            // executed against the recreated swapchain
+                            pending_build = None;
+                        }
+
+                        // usually already finished: the worker had this whole frame's
+                        // GPU execute + present + vsync wait to build it while the
+                        // main thread was off doing other things instead of blocking
+                        let frame = match pending_build.take() {
+                            Some(frame) => frame,
+                            None => match acquire_frame(&swapchain, &images, &image_sets, &compute_pipeline, &pool, &start_time) {
+                                Ok(frame) => frame,
+                                Err(AcquireError::OutOfDate) => {
+                                    recreate_swapchain = true;
+                                    return;
+                                }
+                                Err(e) => panic!("failed to acquire next image: {:?}", e),
+                            },
+                        };
+
+                        if frame.suboptimal {
+                            recreate_swapchain = true;
+                        }
+
+                        let image_num = frame.image_num;
+                        let command_buffer = frame.receiver
+                            .recv()
+                            .expect("worker thread dropped the reply channel");
+
+                        let future = previous_frame_end.take().unwrap()
+                            .join(frame.acquire_future)
+                            .then_execute(queue.clone(), command_buffer)
+                            .unwrap()
+                            .then_swapchain_present(queue.clone(), swapchain.clone(), image_num)
+                            .then_signal_fence_and_flush();
+
+                        previous_frame_end = match future {
+                            Ok(future) => Some(future.boxed()),
+                            Err(FlushError::OutOfDate) => {
+                                recreate_swapchain = true;
+                                Some(sync::now(device.clone()).boxed())
+                            }
+                            Err(e) => {
+                                println!("failed to flush future: {:?}", e);
+                                Some(sync::now(device.clone()).boxed())
+                            }
+                        };
+
+                        // kick off the next frame's build now, so the worker
+                        // thread overlaps it with this frame's execute/present
+                        // above instead of starting from scratch next time
+                        if !recreate_swapchain {
+                            pending_build = match acquire_frame(&swapchain, &images, &image_sets, &compute_pipeline, &pool, &start_time) {
+                                Ok(frame) => Some(frame),
+                                Err(AcquireError::OutOfDate) => {
+                                    recreate_swapchain = true;
+                                    None
+                                }
+                                Err(e) => panic!("failed to acquire next image: {:?}", e),
+                            };
+                        }
+                    }
+                    _ => (),
+                }
+            });
         }
 
         other => {}
@@ -0,0 +1,72 @@
+// Bundles the instance/device/queue every practice mode needs and wraps the
+// buffer-creation and one-time-submit boilerplate that was repeated in each
+// `PracticeType` arm.
+
+use std::sync::Arc;
+
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer};
+use vulkano::device::{Device, Queue};
+use vulkano::sync;
+use vulkano::sync::GpuFuture;
+
+use crate::worker::WorkerPool;
+
+pub struct Context {
+    pub device: Arc<Device>,
+    pub queue: Arc<Queue>,
+}
+
+impl Context {
+    pub fn new(device: Arc<Device>, queue: Arc<Queue>) -> Self {
+        Context { device, queue }
+    }
+
+    /// Allocates a host-accessible buffer sized to `data` and fills it in
+    /// one call, instead of the usual `CpuAccessibleBuffer::from_iter(...)`.
+    pub fn create_buffer_init<T>(&self, data: &[T], usage: BufferUsage) -> Arc<CpuAccessibleBuffer<[T]>>
+    where
+        T: Clone + Send + Sync + 'static,
+    {
+        CpuAccessibleBuffer::from_iter(self.device.clone(), usage, false, data.iter().cloned())
+            .expect("failed to create buffer")
+    }
+
+    /// Allocates a host-accessible buffer of `len` zeroed elements.
+    pub fn create_zeroed_buffer<T>(&self, len: usize, usage: BufferUsage) -> Arc<CpuAccessibleBuffer<[T]>>
+    where
+        T: Default + Clone + Send + Sync + 'static,
+    {
+        CpuAccessibleBuffer::from_iter(self.device.clone(), usage, false, (0..len).map(|_| T::default()))
+            .expect("failed to create buffer")
+    }
+
+    /// Builds a one-time-submit command buffer on `pool` by handing `record`
+    /// a fresh `AutoCommandBufferBuilder` to record into, then submits it and
+    /// blocks until the GPU has finished executing it.
+    pub fn submit_now(
+        &self,
+        pool: &WorkerPool,
+        record: impl FnOnce(&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) + Send + 'static,
+    ) {
+        let command_buffer = pool.submit(move |device, queue| {
+            let mut builder = AutoCommandBufferBuilder::primary(
+                device.clone(),
+                queue.family(),
+                CommandBufferUsage::OneTimeSubmit,
+            ).unwrap();
+
+            record(&mut builder);
+
+            builder.build().unwrap()
+        }).recv().expect("worker thread dropped the reply channel");
+
+        sync::now(self.device.clone())
+            .then_execute(self.queue.clone(), command_buffer)
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap()
+            .wait(None)
+            .unwrap();
+    }
+}
@@ -0,0 +1,106 @@
+// Scores the available physical devices instead of just grabbing the first
+// one, and picks a queue family that actually matches what the caller needs
+// (graphics + presentation, or compute-only).
+
+use std::env;
+
+use vulkano::device::physical::{PhysicalDevice, QueueFamily};
+use vulkano::device::DeviceExtensions;
+use vulkano::device::physical::PhysicalDeviceType;
+use vulkano::instance::Instance;
+use vulkano::swapchain::Surface;
+use winit::window::Window;
+
+/// What the caller needs the selected queue family to be able to do.
+pub enum QueueCapability<'a> {
+    /// Graphics + presentation to the given surface.
+    GraphicsPresent(&'a Surface<Window>),
+    /// Graphics only, no presentation (e.g. offscreen rendering).
+    Graphics,
+    /// Compute dispatch only.
+    Compute,
+}
+
+/// Picks a physical device, a matching queue family, and the device
+/// extensions to request.
+///
+/// Candidates are filtered down to devices whose `supported_extensions()`
+/// is a superset of `required_extensions` unioned with the device's own
+/// `required_extensions()` (e.g. `khr_portability_subset` on
+/// portability/MoltenVK-class devices), then scored (discrete GPUs and
+/// devices with more queues win) and the best one is returned, together
+/// with that same union so the caller passes it to `Device::new` instead
+/// of just the extensions it asked for. Set the `VULKANO_DEVICE_INDEX`
+/// environment variable to force a specific device by its index in
+/// `PhysicalDevice::enumerate`, bypassing the scoring.
+pub fn select_device<'a>(
+    instance: &'a Instance,
+    required_extensions: &DeviceExtensions,
+    capability: QueueCapability,
+) -> (PhysicalDevice<'a>, QueueFamily<'a>, DeviceExtensions) {
+    if let Ok(index) = env::var("VULKANO_DEVICE_INDEX") {
+        let index: usize = index.parse().expect("VULKANO_DEVICE_INDEX must be a number");
+        let physical = PhysicalDevice::from_index(instance, index)
+            .expect("VULKANO_DEVICE_INDEX is out of range");
+        let family = find_queue_family(physical, &capability)
+            .expect("forced device has no queue family matching the requested capability");
+        let extensions = required_extensions.union(physical.required_extensions());
+        return (physical, family, extensions);
+    }
+
+    PhysicalDevice::enumerate(instance)
+        .filter_map(|p| {
+            let extensions = required_extensions.union(p.required_extensions());
+            if !p.supported_extensions().is_superset_of(&extensions) {
+                return None;
+            }
+            find_queue_family(p, &capability).map(|family| (p, family, extensions))
+        })
+        .max_by_key(|(p, family, _)| score_device(p, family))
+        .expect("no suitable physical device available")
+}
+
+fn find_queue_family<'a>(
+    physical: PhysicalDevice<'a>,
+    capability: &QueueCapability,
+) -> Option<QueueFamily<'a>> {
+    physical.queue_families().find(|q| match capability {
+        QueueCapability::GraphicsPresent(surface) => {
+            q.supports_graphics() && q.supports_surface(surface).unwrap_or(false)
+        }
+        QueueCapability::Graphics => q.supports_graphics(),
+        QueueCapability::Compute => q.supports_compute(),
+    })
+}
+
+fn score_device(physical: &PhysicalDevice, family: &QueueFamily) -> u32 {
+    let type_score = match physical.properties().device_type {
+        PhysicalDeviceType::DiscreteGpu => 400,
+        PhysicalDeviceType::IntegratedGpu => 300,
+        PhysicalDeviceType::VirtualGpu => 200,
+        PhysicalDeviceType::Cpu => 100,
+        PhysicalDeviceType::Other => 0,
+    };
+
+    let memory_score: u32 = physical.memory_heaps()
+        .map(|heap| (heap.size() / (1024 * 1024 * 1024)) as u32)
+        .sum();
+
+    type_score + memory_score + family.queues_count() as u32
+}
+
+/// The device extensions this program needs on top of whatever the caller
+/// already requires, unioned together before `Device::new` is called.
+pub fn required_extensions_for(capability: &QueueCapability) -> DeviceExtensions {
+    match capability {
+        QueueCapability::GraphicsPresent(_) => DeviceExtensions {
+            khr_swapchain: true,
+            ..DeviceExtensions::none()
+        },
+        QueueCapability::Graphics => DeviceExtensions::none(),
+        QueueCapability::Compute => DeviceExtensions {
+            khr_storage_buffer_storage_class: true,
+            ..DeviceExtensions::none()
+        },
+    }
+}
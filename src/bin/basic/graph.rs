@@ -0,0 +1,151 @@
+// A tiny task graph: instead of hand-writing a chain of
+// `builder.bind_pipeline_compute(...).dispatch(...).copy_image_to_buffer(...)`
+// calls and reasoning about image layouts ourselves, we register each step
+// as a `Task` that declares which images it touches and what layout it
+// needs them in. `Graph::compile` orders the tasks by their resource
+// dependencies and only emits a layout transition when a task's image
+// isn't already in the layout it needs.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use vulkano::image::{ImageAccess, ImageLayout};
+
+pub type ResourceId = &'static str;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// One image a task reads from or writes to, and the layout it needs to be
+/// in while the task runs.
+pub struct ImageUse {
+    pub id: ResourceId,
+    pub image: Arc<dyn ImageAccess>,
+    pub access: Access,
+    pub layout: ImageLayout,
+}
+
+impl ImageUse {
+    pub fn new(id: ResourceId, image: Arc<dyn ImageAccess>, access: Access, layout: ImageLayout) -> Self {
+        ImageUse { id, image, access, layout }
+    }
+}
+
+/// A single pass: the images it touches plus the closure that records its
+/// commands into the command buffer builder, assuming its declared images
+/// are already in the right layout. Buffers are not declared or tracked.
+pub struct Task<B> {
+    uses: Vec<ImageUse>,
+    apply: Box<dyn FnOnce(&mut B) + Send>,
+}
+
+impl<B> Task<B> {
+    pub fn new(uses: Vec<ImageUse>, apply: impl FnOnce(&mut B) + Send + 'static) -> Self {
+        Task { uses, apply: Box::new(apply) }
+    }
+}
+
+pub struct Graph<B> {
+    tasks: Vec<Task<B>>,
+}
+
+impl<B> Graph<B> {
+    pub fn new() -> Self {
+        Graph { tasks: Vec::new() }
+    }
+
+    pub fn add_task(&mut self, task: Task<B>) {
+        self.tasks.push(task);
+    }
+
+    /// Orders the registered tasks so that any task touching a resource
+    /// another task already wrote to runs after it, then feeds each task's
+    /// builder closure to `transition`/the closure itself in that order.
+    /// `transition` is called only when a resource's tracked layout differs
+    /// from what the next task needs it in.
+    pub fn compile(
+        self,
+        builder: &mut B,
+        mut transition: impl FnMut(&mut B, &Arc<dyn ImageAccess>, ImageLayout, ImageLayout),
+    ) {
+        let order = topological_order(&self.tasks);
+        let mut current_layout: HashMap<ResourceId, ImageLayout> = HashMap::new();
+        let mut tasks: Vec<Option<Task<B>>> = self.tasks.into_iter().map(Some).collect();
+
+        for index in order {
+            let task = tasks[index].take().expect("each task index appears once in the order");
+
+            for use_ in &task.uses {
+                let from = current_layout.get(use_.id).copied().unwrap_or(ImageLayout::Undefined);
+                if from != use_.layout {
+                    transition(builder, &use_.image, from, use_.layout);
+                    current_layout.insert(use_.id, use_.layout);
+                }
+            }
+
+            (task.apply)(builder);
+        }
+    }
+}
+
+/// Kahn's algorithm over the dependency edges implied by resource accesses:
+/// a read depends on the last earlier write of the same resource, and a
+/// write depends on that last write plus every read of the resource since
+/// it (a write must be ordered after anything before it, and anything after
+/// a write must be ordered after it; two reads in a row don't need ordering).
+fn topological_order<B>(tasks: &[Task<B>]) -> Vec<usize> {
+    let mut last_writer: HashMap<ResourceId, usize> = HashMap::new();
+    let mut readers_since_write: HashMap<ResourceId, Vec<usize>> = HashMap::new();
+    let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); tasks.len()];
+    let mut in_degree = vec![0usize; tasks.len()];
+
+    for (index, task) in tasks.iter().enumerate() {
+        for use_ in &task.uses {
+            // RAW/WAW: both reads and writes wait on the last write
+            if let Some(&writer) = last_writer.get(use_.id) {
+                dependents[writer].push(index);
+                in_degree[index] += 1;
+            }
+
+            match use_.access {
+                Access::Read => {
+                    readers_since_write.entry(use_.id).or_default().push(index);
+                }
+                Access::Write => {
+                    // WAR: a write also waits on every read since the last write;
+                    // reads among themselves never get an edge
+                    for &reader in readers_since_write.get(use_.id).into_iter().flatten() {
+                        dependents[reader].push(index);
+                        in_degree[index] += 1;
+                    }
+                    readers_since_write.insert(use_.id, Vec::new());
+                    last_writer.insert(use_.id, index);
+                }
+            }
+        }
+    }
+
+    let mut ready: Vec<usize> = (0..tasks.len()).filter(|&i| in_degree[i] == 0).collect();
+    let mut order = Vec::with_capacity(tasks.len());
+
+    while !ready.is_empty() {
+        // stable: always take the earliest-registered ready task so tasks
+        // with no dependency on each other keep their registration order
+        ready.sort_unstable();
+        let index = ready.remove(0);
+        order.push(index);
+
+        for &next in &dependents[index] {
+            in_degree[next] -= 1;
+            if in_degree[next] == 0 {
+                ready.push(next);
+            }
+        }
+    }
+
+    assert_eq!(order.len(), tasks.len(), "resource accesses form a dependency cycle");
+    order
+}
@@ -0,0 +1,83 @@
+// Thread pool that builds command buffers off the main thread.
+//
+// Each worker owns its own clone of the `Device`/`Queue` (vulkano hands these
+// out as `Arc`s already, so cloning is cheap) and runs closures handed to it
+// through `submit`. The finished `PrimaryAutoCommandBuffer` comes back on a
+// one-shot channel so the caller can still decide when/how to submit it to
+// the GPU.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use vulkano::command_buffer::PrimaryAutoCommandBuffer;
+use vulkano::device::{Device, Queue};
+
+type Job = Box<dyn FnOnce(&Arc<Device>, &Arc<Queue>) -> PrimaryAutoCommandBuffer + Send>;
+
+struct Task {
+    job: Job,
+    reply: Sender<PrimaryAutoCommandBuffer>,
+}
+
+pub struct WorkerPool {
+    senders: Vec<Sender<Task>>,
+    next: AtomicUsize,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl WorkerPool {
+    pub fn new(device: Arc<Device>, queue: Arc<Queue>, num_workers: usize) -> Self {
+        let mut senders = Vec::with_capacity(num_workers);
+        let mut handles = Vec::with_capacity(num_workers);
+
+        for _ in 0..num_workers {
+            let (tx, rx) = mpsc::channel::<Task>();
+            let device = device.clone();
+            let queue = queue.clone();
+
+            let handle = thread::spawn(move || {
+                for task in rx.iter() {
+                    let command_buffer = (task.job)(&device, &queue);
+                    // the receiver may have stopped waiting (e.g. the frame
+                    // it was built for was discarded); that's not this
+                    // worker's problem
+                    let _ = task.reply.send(command_buffer);
+                }
+            });
+
+            senders.push(tx);
+            handles.push(handle);
+        }
+
+        WorkerPool { senders, next: AtomicUsize::new(0), handles }
+    }
+
+    /// Hands `build` to the next worker in round-robin order and returns a
+    /// receiver that yields the finished command buffer once it's built.
+    pub fn submit<F>(&self, build: F) -> Receiver<PrimaryAutoCommandBuffer>
+    where
+        F: FnOnce(&Arc<Device>, &Arc<Queue>) -> PrimaryAutoCommandBuffer + Send + 'static,
+    {
+        let (reply_tx, reply_rx) = mpsc::channel();
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.senders.len();
+
+        self.senders[index]
+            .send(Task { job: Box::new(build), reply: reply_tx })
+            .expect("worker thread panicked");
+
+        reply_rx
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        // dropping the senders closes each worker's channel, which breaks it
+        // out of `rx.iter()` and ends the thread
+        self.senders.clear();
+        for handle in self.handles.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}